@@ -0,0 +1,86 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2020 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Error types used throughout the library.
+
+use std::fmt;
+
+/// Errors specific to communicating with (or configuring) a device.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ButtplugDeviceError {
+  /// A protocol implementation hit a condition it couldn't recover from. The
+  /// first field is the protocol name, the second is a human readable
+  /// description of what went wrong.
+  ProtocolSpecificError(&'static str, &'static str),
+  /// A message was sent to a device that doesn't support it.
+  ProtocolNotImplemented(String),
+  /// The device attributes required for a command (e.g. a feature index)
+  /// don't exist on this device.
+  DeviceNotAvailable(String),
+}
+
+impl fmt::Display for ButtplugDeviceError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ButtplugDeviceError::ProtocolSpecificError(protocol, error) => {
+        write!(f, "{}: {}", protocol, error)
+      }
+      ButtplugDeviceError::ProtocolNotImplemented(msg) => write!(f, "{}", msg),
+      ButtplugDeviceError::DeviceNotAvailable(msg) => write!(f, "{}", msg),
+    }
+  }
+}
+
+/// Errors related to malformed or unsupported `ButtplugMessage` instances.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ButtplugMessageError {
+  /// The message isn't part of the message set that was negotiated for the
+  /// connection (see spec-version downgrading in
+  /// `core::message_downgrade`).
+  MessageNotSupported(String),
+  /// The message failed some other form of validation.
+  InvalidMessageContents(String),
+}
+
+impl fmt::Display for ButtplugMessageError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ButtplugMessageError::MessageNotSupported(msg) => write!(f, "{}", msg),
+      ButtplugMessageError::InvalidMessageContents(msg) => write!(f, "{}", msg),
+    }
+  }
+}
+
+/// Top level error type returned across the public API surface.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ButtplugError {
+  ButtplugDeviceError(ButtplugDeviceError),
+  ButtplugMessageError(ButtplugMessageError),
+}
+
+impl fmt::Display for ButtplugError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ButtplugError::ButtplugDeviceError(e) => write!(f, "{}", e),
+      ButtplugError::ButtplugMessageError(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl std::error::Error for ButtplugError {}
+
+impl From<ButtplugDeviceError> for ButtplugError {
+  fn from(error: ButtplugDeviceError) -> Self {
+    ButtplugError::ButtplugDeviceError(error)
+  }
+}
+
+impl From<ButtplugMessageError> for ButtplugError {
+  fn from(error: ButtplugMessageError) -> Self {
+    ButtplugError::ButtplugMessageError(error)
+  }
+}
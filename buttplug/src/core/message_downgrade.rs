@@ -0,0 +1,227 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2020 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Message spec-version negotiation.
+//!
+//! Older clients only understand the legacy `VibrateCmd`/`RotateCmd`
+//! message set (spec versions 1 and 2); newer clients also understand
+//! `ScalarCmd`/`SensorReadCmd` (spec version 3 onward). Rather than make
+//! every protocol aware of which version a given client negotiated, the
+//! server runs device attributes through `downgrade_device_attributes`
+//! before sending them out, and checks incoming commands against
+//! `validate_message_for_version` before dispatching them to a protocol.
+
+use crate::core::{
+  errors::{ButtplugError, ButtplugMessageError},
+  messages::{
+    ActuatorType,
+    ButtplugDeviceCommandMessageUnion,
+    ButtplugDeviceMessageType,
+    MessageAttributes,
+    MessageAttributesMap,
+    RequestServerInfo,
+    ServerInfo,
+  },
+};
+
+/// The latest spec version this server implements.
+pub const CURRENT_MESSAGE_SPEC_VERSION: u32 = 3;
+
+/// Handles the `RequestServerInfo`/`ServerInfo` handshake exchange,
+/// negotiating the lesser of what the client asked for and what this server
+/// supports. The returned version is what every later
+/// `validate_message_for_version`/`downgrade_device_attributes` call for the
+/// connection should be passed.
+pub fn negotiate_spec_version(request: &RequestServerInfo) -> ServerInfo {
+  ServerInfo::new(request.message_version.min(CURRENT_MESSAGE_SPEC_VERSION))
+}
+
+/// The spec version a message type was introduced in.
+fn minimum_spec_version(message_type: ButtplugDeviceMessageType) -> u32 {
+  match message_type {
+    ButtplugDeviceMessageType::VibrateCmd => 1,
+    ButtplugDeviceMessageType::RotateCmd => 2,
+    ButtplugDeviceMessageType::ScalarCmd | ButtplugDeviceMessageType::SensorReadCmd => 3,
+  }
+}
+
+/// Checks that `message_type` is legal to send/receive under
+/// `spec_version`, erroring out otherwise.
+pub fn validate_message_version(
+  message_type: ButtplugDeviceMessageType,
+  spec_version: u32,
+) -> Result<(), ButtplugError> {
+  if spec_version < minimum_spec_version(message_type) {
+    Err(
+      ButtplugMessageError::MessageNotSupported(format!(
+        "{:?} is not part of message spec version {}",
+        message_type, spec_version
+      ))
+      .into(),
+    )
+  } else {
+    Ok(())
+  }
+}
+
+/// Validates an incoming device command against the spec version negotiated
+/// for the connection it arrived on.
+pub fn validate_message_for_version(
+  command: &ButtplugDeviceCommandMessageUnion,
+  spec_version: u32,
+) -> Result<(), ButtplugError> {
+  match command.message_type() {
+    Some(message_type) => validate_message_version(message_type, spec_version),
+    None => Ok(()),
+  }
+}
+
+/// Rewrites a device's message attributes for an older spec version,
+/// synthesizing legacy message descriptions from newer ones where possible.
+/// Called on every `MessageAttributesMap` before it's handed to a client.
+pub fn downgrade_device_attributes(
+  attrs: &MessageAttributesMap,
+  spec_version: u32,
+) -> MessageAttributesMap {
+  let mut downgraded: MessageAttributesMap = attrs
+    .iter()
+    .filter(|(message_type, _)| validate_message_version(**message_type, spec_version).is_ok())
+    .map(|(message_type, message_attrs)| (*message_type, message_attrs.clone()))
+    .collect();
+
+  if spec_version < 3 {
+    if let Some(scalar_attrs) = attrs.get(&ButtplugDeviceMessageType::ScalarCmd) {
+      if let Some(vibrate_attrs) = synthesize_vibrate_cmd(scalar_attrs) {
+        downgraded.insert(ButtplugDeviceMessageType::VibrateCmd, vibrate_attrs);
+      }
+    }
+  }
+
+  downgraded
+}
+
+/// Builds a `VibrateCmd` attribute entry out of the vibrate-capable
+/// features of a `ScalarCmd` attribute entry. Returns `None` if the device
+/// has no vibrate actuators at all (e.g. a rotate-only device), since a
+/// zero-motor `VibrateCmd` entry crashes strict v1/v2 clients.
+fn synthesize_vibrate_cmd(scalar_attrs: &MessageAttributes) -> Option<MessageAttributes> {
+  let actuator_types = scalar_attrs.actuator_types.as_ref()?;
+  let step_counts = scalar_attrs.step_count.as_ref()?;
+  let vibrate_step_counts: Vec<u32> = actuator_types
+    .iter()
+    .zip(step_counts.iter())
+    .filter(|(actuator_type, _)| **actuator_type == ActuatorType::Vibrate)
+    .map(|(_, step_count)| *step_count)
+    .collect();
+  if vibrate_step_counts.is_empty() {
+    return None;
+  }
+  Some(MessageAttributes {
+    feature_count: Some(vibrate_step_counts.len() as u32),
+    step_count: Some(vibrate_step_counts),
+    actuator_types: None,
+    sensor_types: None,
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::core::messages::SensorType;
+
+  fn multi_actuator_attrs() -> MessageAttributesMap {
+    let mut attrs = MessageAttributesMap::new();
+    attrs.insert(
+      ButtplugDeviceMessageType::ScalarCmd,
+      MessageAttributes {
+        feature_count: Some(2),
+        step_count: Some(vec![20, 100]),
+        actuator_types: Some(vec![ActuatorType::Rotate, ActuatorType::Vibrate]),
+        sensor_types: None,
+      },
+    );
+    attrs.insert(
+      ButtplugDeviceMessageType::SensorReadCmd,
+      MessageAttributes {
+        feature_count: Some(1),
+        step_count: None,
+        actuator_types: None,
+        sensor_types: Some(vec![SensorType::Battery]),
+      },
+    );
+    attrs
+  }
+
+  #[test]
+  fn test_downgrade_synthesizes_vibrate_cmd() {
+    let downgraded = downgrade_device_attributes(&multi_actuator_attrs(), 1);
+    assert!(!downgraded.contains_key(&ButtplugDeviceMessageType::ScalarCmd));
+    assert!(!downgraded.contains_key(&ButtplugDeviceMessageType::SensorReadCmd));
+    let vibrate_attrs = downgraded
+      .get(&ButtplugDeviceMessageType::VibrateCmd)
+      .expect("vibrate-capable device should get a synthesized VibrateCmd entry");
+    assert_eq!(vibrate_attrs.feature_count, Some(1));
+    assert_eq!(vibrate_attrs.step_count, Some(vec![100]));
+  }
+
+  #[test]
+  fn test_downgrade_drops_phantom_vibrate_cmd_for_rotate_only_device() {
+    let mut attrs = MessageAttributesMap::new();
+    attrs.insert(
+      ButtplugDeviceMessageType::ScalarCmd,
+      MessageAttributes {
+        feature_count: Some(1),
+        step_count: Some(vec![20]),
+        actuator_types: Some(vec![ActuatorType::Rotate]),
+        sensor_types: None,
+      },
+    );
+    let downgraded = downgrade_device_attributes(&attrs, 1);
+    assert!(!downgraded.contains_key(&ButtplugDeviceMessageType::VibrateCmd));
+  }
+
+  #[test]
+  fn test_validate_message_for_version_rejects_unsupported_type() {
+    use crate::core::messages::{ScalarCmd, ScalarSubcommand};
+    let cmd = ButtplugDeviceCommandMessageUnion::ScalarCmd(ScalarCmd::new(
+      0,
+      vec![ScalarSubcommand::new(0, 0.5, ActuatorType::Vibrate)],
+    ));
+    assert!(validate_message_for_version(&cmd, 1).is_err());
+    assert!(validate_message_for_version(&cmd, 3).is_ok());
+  }
+
+  #[test]
+  fn test_validate_message_for_version_rejects_rotate_cmd_under_v1() {
+    use crate::core::messages::{RotateCmd, RotateSubcommand};
+    let cmd =
+      ButtplugDeviceCommandMessageUnion::RotateCmd(RotateCmd::new(0, vec![RotateSubcommand::new(0, 0.5, true)]));
+    assert!(validate_message_for_version(&cmd, 1).is_err());
+    assert!(validate_message_for_version(&cmd, 2).is_ok());
+  }
+
+  #[test]
+  fn test_negotiated_v1_handshake_downgrades_multi_actuator_device() {
+    let request = RequestServerInfo::new("legacy-client", 1);
+    let server_info = negotiate_spec_version(&request);
+    assert_eq!(server_info.message_version, 1);
+
+    let downgraded = downgrade_device_attributes(&multi_actuator_attrs(), server_info.message_version);
+
+    // Only the legacy VibrateCmd field should survive; ScalarCmd, RotateCmd
+    // and SensorReadCmd are all v2/v3-and-later additions.
+    assert_eq!(downgraded.len(), 1);
+    let vibrate_attrs = downgraded
+      .get(&ButtplugDeviceMessageType::VibrateCmd)
+      .expect("vibrate-capable device should get a synthesized VibrateCmd entry");
+    assert_eq!(vibrate_attrs.feature_count, Some(1));
+    assert_eq!(vibrate_attrs.step_count, Some(vec![100]));
+    assert!(!downgraded.contains_key(&ButtplugDeviceMessageType::RotateCmd));
+    assert!(!downgraded.contains_key(&ButtplugDeviceMessageType::ScalarCmd));
+    assert!(!downgraded.contains_key(&ButtplugDeviceMessageType::SensorReadCmd));
+  }
+}
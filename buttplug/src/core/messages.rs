@@ -0,0 +1,326 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2020 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! The Buttplug message set.
+//!
+//! This module holds all of the messages that can be sent to and from a
+//! `ButtplugDevice`, along with the attribute maps servers use to advertise
+//! which messages a given device supports.
+
+use std::collections::HashMap;
+
+/// Identifies which message type a `MessageAttributes` entry describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ButtplugDeviceMessageType {
+  VibrateCmd,
+  RotateCmd,
+  ScalarCmd,
+  SensorReadCmd,
+}
+
+/// The type of actuator a `ScalarSubcommand` addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActuatorType {
+  Vibrate,
+  Rotate,
+  Oscillate,
+  Constrict,
+  Inflate,
+}
+
+/// The type of sensor a `SensorReadCmd` addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensorType {
+  Battery,
+  RSSI,
+  Pressure,
+}
+
+/// Per-feature information the server uses to tell clients what a device
+/// message can do (step count, number of actuators/sensors, etc).
+#[derive(Debug, Clone, Default)]
+pub struct MessageAttributes {
+  pub feature_count: Option<u32>,
+  pub step_count: Option<Vec<u32>>,
+  pub actuator_types: Option<Vec<ActuatorType>>,
+  pub sensor_types: Option<Vec<SensorType>>,
+}
+
+/// Map of which messages (and their attributes) a device supports, keyed by
+/// message type. Built from the device configuration file and handed to
+/// protocols/clients so they know what a device can do.
+pub type MessageAttributesMap = HashMap<ButtplugDeviceMessageType, MessageAttributes>;
+
+/// Trait for Buttplug protocol messages that address a device by index. The
+/// message structs in this module do not implement it.
+pub trait ButtplugMessage: Clone {
+  fn device_index(&self) -> u32;
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ok {}
+
+impl Ok {
+  pub fn default() -> Self {
+    Self {}
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VibrateSubcommand {
+  pub index: u32,
+  pub speed: f64,
+}
+
+impl VibrateSubcommand {
+  pub fn new(index: u32, speed: f64) -> Self {
+    Self { index, speed }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VibrateCmd {
+  pub device_index: u32,
+  pub speeds: Vec<VibrateSubcommand>,
+}
+
+impl VibrateCmd {
+  pub fn new(device_index: u32, speeds: Vec<VibrateSubcommand>) -> Self {
+    Self {
+      device_index,
+      speeds,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RotateSubcommand {
+  pub index: u32,
+  pub speed: f64,
+  pub clockwise: bool,
+}
+
+impl RotateSubcommand {
+  pub fn new(index: u32, speed: f64, clockwise: bool) -> Self {
+    Self {
+      index,
+      speed,
+      clockwise,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RotateCmd {
+  pub device_index: u32,
+  pub rotations: Vec<RotateSubcommand>,
+}
+
+impl RotateCmd {
+  pub fn new(device_index: u32, rotations: Vec<RotateSubcommand>) -> Self {
+    Self {
+      device_index,
+      rotations,
+    }
+  }
+}
+
+/// A single actuator target within a `ScalarCmd`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScalarSubcommand {
+  pub index: u32,
+  pub scalar: f64,
+  pub actuator_type: ActuatorType,
+}
+
+impl ScalarSubcommand {
+  pub fn new(index: u32, scalar: f64, actuator_type: ActuatorType) -> Self {
+    Self {
+      index,
+      scalar,
+      actuator_type,
+    }
+  }
+}
+
+/// Unified actuator command, covering vibrate/rotate/oscillate/constrict/
+/// inflate style hardware through a single message type. Protocols that
+/// don't implement `handle_scalar_cmd` directly get it decomposed into the
+/// legacy `VibrateCmd`/`RotateCmd` messages for free (see
+/// `ButtplugProtocolCommandHandler::handle_scalar_cmd`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScalarCmd {
+  pub device_index: u32,
+  pub scalars: Vec<ScalarSubcommand>,
+}
+
+impl ScalarCmd {
+  pub fn new(device_index: u32, scalars: Vec<ScalarSubcommand>) -> Self {
+    Self {
+      device_index,
+      scalars,
+    }
+  }
+}
+
+/// Requests a reading from a single sensor on a device (e.g. battery level).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensorReadCmd {
+  pub device_index: u32,
+  pub sensor_index: u32,
+  pub sensor_type: SensorType,
+}
+
+impl SensorReadCmd {
+  pub fn new(device_index: u32, sensor_index: u32, sensor_type: SensorType) -> Self {
+    Self {
+      device_index,
+      sensor_index,
+      sensor_type,
+    }
+  }
+}
+
+/// Reply to a `SensorReadCmd`, carrying the raw reading data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensorReading {
+  pub device_index: u32,
+  pub sensor_index: u32,
+  pub sensor_type: SensorType,
+  pub data: Vec<i32>,
+}
+
+impl SensorReading {
+  pub fn new(device_index: u32, sensor_index: u32, sensor_type: SensorType, data: Vec<i32>) -> Self {
+    Self {
+      device_index,
+      sensor_index,
+      sensor_type,
+      data,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StopDeviceCmd {
+  pub device_index: u32,
+}
+
+impl StopDeviceCmd {
+  pub fn new(device_index: u32) -> Self {
+    Self { device_index }
+  }
+}
+
+/// Union of every message a `ButtplugProtocolCommandHandler` may be asked to
+/// handle. Protocol-facing code converts specific message structs into this
+/// via `.into()` before handing them to `parse_message`/command handlers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ButtplugDeviceCommandMessageUnion {
+  VibrateCmd(VibrateCmd),
+  RotateCmd(RotateCmd),
+  ScalarCmd(ScalarCmd),
+  SensorReadCmd(SensorReadCmd),
+  StopDeviceCmd(StopDeviceCmd),
+}
+
+impl ButtplugDeviceCommandMessageUnion {
+  /// The message type this command is carrying, for spec-version
+  /// validation. `StopDeviceCmd` has no type of its own (it's legal in
+  /// every spec version), so it maps to `None`.
+  pub fn message_type(&self) -> Option<ButtplugDeviceMessageType> {
+    match self {
+      ButtplugDeviceCommandMessageUnion::VibrateCmd(_) => Some(ButtplugDeviceMessageType::VibrateCmd),
+      ButtplugDeviceCommandMessageUnion::RotateCmd(_) => Some(ButtplugDeviceMessageType::RotateCmd),
+      ButtplugDeviceCommandMessageUnion::ScalarCmd(_) => Some(ButtplugDeviceMessageType::ScalarCmd),
+      ButtplugDeviceCommandMessageUnion::SensorReadCmd(_) => {
+        Some(ButtplugDeviceMessageType::SensorReadCmd)
+      }
+      ButtplugDeviceCommandMessageUnion::StopDeviceCmd(_) => None,
+    }
+  }
+}
+
+/// Sent by a client as the first message of a connection, negotiating which
+/// message spec version the rest of the session will speak.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestServerInfo {
+  pub client_name: String,
+  pub message_version: u32,
+}
+
+impl RequestServerInfo {
+  pub fn new(client_name: &str, message_version: u32) -> Self {
+    Self {
+      client_name: client_name.to_owned(),
+      message_version,
+    }
+  }
+}
+
+/// Server's reply to `RequestServerInfo`, carrying the spec version that was
+/// actually negotiated (the lesser of what the client asked for and what the
+/// server supports).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerInfo {
+  pub message_version: u32,
+}
+
+impl ServerInfo {
+  pub fn new(message_version: u32) -> Self {
+    Self { message_version }
+  }
+}
+
+/// Union of every reply a device command can produce.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ButtplugServerMessage {
+  Ok(Ok),
+  SensorReading(SensorReading),
+}
+
+impl From<Ok> for ButtplugServerMessage {
+  fn from(msg: Ok) -> Self {
+    ButtplugServerMessage::Ok(msg)
+  }
+}
+
+impl From<SensorReading> for ButtplugServerMessage {
+  fn from(msg: SensorReading) -> Self {
+    ButtplugServerMessage::SensorReading(msg)
+  }
+}
+
+impl From<VibrateCmd> for ButtplugDeviceCommandMessageUnion {
+  fn from(msg: VibrateCmd) -> Self {
+    ButtplugDeviceCommandMessageUnion::VibrateCmd(msg)
+  }
+}
+
+impl From<RotateCmd> for ButtplugDeviceCommandMessageUnion {
+  fn from(msg: RotateCmd) -> Self {
+    ButtplugDeviceCommandMessageUnion::RotateCmd(msg)
+  }
+}
+
+impl From<ScalarCmd> for ButtplugDeviceCommandMessageUnion {
+  fn from(msg: ScalarCmd) -> Self {
+    ButtplugDeviceCommandMessageUnion::ScalarCmd(msg)
+  }
+}
+
+impl From<SensorReadCmd> for ButtplugDeviceCommandMessageUnion {
+  fn from(msg: SensorReadCmd) -> Self {
+    ButtplugDeviceCommandMessageUnion::SensorReadCmd(msg)
+  }
+}
+
+impl From<StopDeviceCmd> for ButtplugDeviceCommandMessageUnion {
+  fn from(msg: StopDeviceCmd) -> Self {
+    ButtplugDeviceCommandMessageUnion::StopDeviceCmd(msg)
+  }
+}
@@ -8,6 +8,7 @@
 //! Protocol message and error definitions.
 
 pub mod errors;
+pub mod message_downgrade;
 pub mod messages;
 
 use errors::ButtplugError;
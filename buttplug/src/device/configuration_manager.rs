@@ -0,0 +1,63 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2020 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Per-protocol device configuration, as loaded from the device
+//! configuration file. Maps the identifier a protocol's handshake discovers
+//! (e.g. a Lovense device type string) to the display names and message
+//! attributes the server should advertise for that device.
+
+use crate::core::messages::MessageAttributesMap;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Locale -> display name map, as stored per device identifier.
+pub type DeviceNameMap = HashMap<String, String>;
+
+/// How long a protocol's `try_create` handshake gets to hear back from the
+/// device before giving up, if the device configuration doesn't specify its
+/// own value.
+pub const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The slice of device configuration a protocol needs to finish
+/// initialization: per-identifier names and message attributes.
+#[derive(Debug, Clone)]
+pub struct DeviceProtocolConfiguration {
+  names: HashMap<String, (DeviceNameMap, MessageAttributesMap)>,
+  handshake_timeout: Duration,
+}
+
+impl Default for DeviceProtocolConfiguration {
+  fn default() -> Self {
+    Self::new(HashMap::new())
+  }
+}
+
+impl DeviceProtocolConfiguration {
+  pub fn new(names: HashMap<String, (DeviceNameMap, MessageAttributesMap)>) -> Self {
+    Self::new_with_handshake_timeout(names, DEFAULT_HANDSHAKE_TIMEOUT)
+  }
+
+  pub fn new_with_handshake_timeout(
+    names: HashMap<String, (DeviceNameMap, MessageAttributesMap)>,
+    handshake_timeout: Duration,
+  ) -> Self {
+    Self {
+      names,
+      handshake_timeout,
+    }
+  }
+
+  pub fn get_attributes(&self, identifier: &str) -> Option<(DeviceNameMap, MessageAttributesMap)> {
+    self.names.get(identifier).cloned()
+  }
+
+  /// How long a protocol handshake waiting on a device response (e.g.
+  /// Lovense's `DeviceType;` probe) should wait before giving up.
+  pub fn handshake_timeout(&self) -> Duration {
+    self.handshake_timeout
+  }
+}
@@ -0,0 +1,168 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2020 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Device communication abstractions: the wire-level endpoints and commands
+//! protocols use, independent of whatever transport (BLE, serial, USB, ...)
+//! actually backs a given device.
+
+pub mod configuration_manager;
+pub mod protocol;
+
+use crate::core::{
+  errors::ButtplugError,
+  message_downgrade::{
+    downgrade_device_attributes,
+    negotiate_spec_version as negotiate_message_spec_version,
+    validate_message_for_version,
+    CURRENT_MESSAGE_SPEC_VERSION,
+  },
+  messages::{
+    ButtplugDeviceCommandMessageUnion,
+    ButtplugServerMessage,
+    MessageAttributesMap,
+    RequestServerInfo,
+    ServerInfo,
+  },
+};
+use futures::future::BoxFuture;
+use protocol::ButtplugProtocolProperties;
+use std::sync::{
+  atomic::{AtomicU32, Ordering},
+  Arc,
+};
+
+/// The wire-level channel a command is written to or read from. Exact
+/// meaning is backend specific (a BLE characteristic, a serial port, etc).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+  Tx,
+  Rx,
+  Command,
+  Firmware,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceWriteCmd {
+  pub endpoint: Endpoint,
+  pub data: Vec<u8>,
+  pub write_with_response: bool,
+}
+
+impl DeviceWriteCmd {
+  pub fn new(endpoint: Endpoint, data: Vec<u8>, write_with_response: bool) -> Self {
+    Self {
+      endpoint,
+      data,
+      write_with_response,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceSubscribeCmd {
+  pub endpoint: Endpoint,
+}
+
+impl DeviceSubscribeCmd {
+  pub fn new(endpoint: Endpoint) -> Self {
+    Self { endpoint }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceUnsubscribeCmd {
+  pub endpoint: Endpoint,
+}
+
+impl DeviceUnsubscribeCmd {
+  pub fn new(endpoint: Endpoint) -> Self {
+    Self { endpoint }
+  }
+}
+
+/// Commands that can be issued against a `DeviceImpl`. Test backends record
+/// these so assertions can check what a protocol actually sent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceImplCommand {
+  Write(DeviceWriteCmd),
+  Subscribe(DeviceSubscribeCmd),
+  Unsubscribe(DeviceUnsubscribeCmd),
+}
+
+/// Out-of-band events a device can emit once subscribed (notifications,
+/// disconnects).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ButtplugDeviceEvent {
+  Notification(Endpoint, Vec<u8>),
+  Removed,
+}
+
+pub type ButtplugDeviceReturn = Result<(), ButtplugError>;
+
+/// Transport-agnostic handle to a connected device. BLE/serial/USB backends
+/// (and the test harness) all implement this.
+pub trait DeviceImpl: Send + Sync {
+  fn write_value(&self, msg: DeviceWriteCmd) -> BoxFuture<'static, ButtplugDeviceReturn>;
+  fn subscribe(&self, msg: DeviceSubscribeCmd) -> BoxFuture<'static, ButtplugDeviceReturn>;
+  fn unsubscribe(&self, msg: DeviceUnsubscribeCmd) -> BoxFuture<'static, ButtplugDeviceReturn>;
+  fn get_event_receiver(&self) -> futures::channel::mpsc::UnboundedReceiver<ButtplugDeviceEvent>;
+}
+
+/// A device once it's been matched up with the protocol that speaks its
+/// language. This is what clients actually send messages to; it hides the
+/// transport (`DeviceImpl`) and the protocol translation behind a single
+/// `parse_message` call.
+pub struct ButtplugDevice {
+  device_impl: Arc<Box<dyn DeviceImpl>>,
+  protocol: Box<dyn protocol::ButtplugProtocol>,
+  /// The message spec version negotiated for this device's connection.
+  /// Defaults to the latest spec version until `negotiate_spec_version` is
+  /// called with the client's `RequestServerInfo`.
+  spec_version: AtomicU32,
+}
+
+impl ButtplugDevice {
+  pub fn new(device_impl: Box<dyn DeviceImpl>, protocol: Box<dyn protocol::ButtplugProtocol>) -> Self {
+    Self {
+      device_impl: Arc::new(device_impl),
+      protocol,
+      spec_version: AtomicU32::new(CURRENT_MESSAGE_SPEC_VERSION),
+    }
+  }
+
+  /// Handles this device's side of the `RequestServerInfo`/`ServerInfo`
+  /// handshake, storing the negotiated spec version so later
+  /// `parse_message`/`message_attributes` calls validate and downgrade
+  /// against it.
+  pub fn negotiate_spec_version(&self, request: &RequestServerInfo) -> ServerInfo {
+    let server_info = negotiate_message_spec_version(request);
+    self
+      .spec_version
+      .store(server_info.message_version, Ordering::SeqCst);
+    server_info
+  }
+
+  /// This device's message attributes, downgraded to match the spec version
+  /// negotiated for its connection.
+  pub fn message_attributes(&self) -> MessageAttributesMap {
+    downgrade_device_attributes(
+      &self.protocol.message_attributes(),
+      self.spec_version.load(Ordering::SeqCst),
+    )
+  }
+
+  pub async fn parse_message(
+    &self,
+    msg: ButtplugDeviceCommandMessageUnion,
+  ) -> Result<ButtplugServerMessage, ButtplugError> {
+    validate_message_for_version(&msg, self.spec_version.load(Ordering::SeqCst))?;
+    self
+      .protocol
+      .handle_command(self.device_impl.clone(), msg)
+      .await
+  }
+}
@@ -0,0 +1,276 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2020 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Shared bookkeeping for protocols built on generic vibrate/rotate style
+//! actuators: tracks the last value sent to each feature so we only write to
+//! the device when something actually changed.
+
+use crate::core::{
+  errors::ButtplugDeviceError,
+  messages::{
+    ActuatorType,
+    ButtplugDeviceCommandMessageUnion,
+    ButtplugDeviceMessageType,
+    MessageAttributesMap,
+    RotateCmd,
+    RotateSubcommand,
+    ScalarCmd,
+    ScalarSubcommand,
+    VibrateCmd,
+    VibrateSubcommand,
+  },
+};
+
+/// Tracks per-feature actuator state for a device, diffing incoming
+/// commands against the last value sent so protocols only need to write out
+/// the values that changed.
+#[derive(Debug, Clone)]
+pub struct GenericCommandManager {
+  vibrations: Vec<Option<u32>>,
+  vibration_step_counts: Vec<u32>,
+  rotations: Vec<Option<(u32, bool)>>,
+  rotation_step_counts: Vec<u32>,
+  scalars: Vec<Option<u32>>,
+  scalar_step_counts: Vec<u32>,
+  scalar_actuator_types: Vec<ActuatorType>,
+}
+
+impl GenericCommandManager {
+  pub fn new(attributes: &MessageAttributesMap) -> Self {
+    let vibration_step_counts = attributes
+      .get(&ButtplugDeviceMessageType::VibrateCmd)
+      .and_then(|attrs| attrs.step_count.clone())
+      .unwrap_or_default();
+    let rotation_step_counts = attributes
+      .get(&ButtplugDeviceMessageType::RotateCmd)
+      .and_then(|attrs| attrs.step_count.clone())
+      .unwrap_or_default();
+    let scalar_attrs = attributes.get(&ButtplugDeviceMessageType::ScalarCmd);
+    let scalar_step_counts = scalar_attrs
+      .and_then(|attrs| attrs.step_count.clone())
+      .unwrap_or_default();
+    let scalar_actuator_types = scalar_attrs
+      .and_then(|attrs| attrs.actuator_types.clone())
+      .unwrap_or_default();
+    Self {
+      vibrations: vec![Some(0); vibration_step_counts.len()],
+      vibration_step_counts,
+      rotations: vec![None; rotation_step_counts.len()],
+      rotation_step_counts,
+      scalars: vec![Some(0); scalar_step_counts.len()],
+      scalar_step_counts,
+      scalar_actuator_types,
+    }
+  }
+
+  /// Diffs `msg` against the last vibration values sent. Returns `None` if
+  /// nothing changed (so the protocol can skip writing to the device
+  /// entirely). Otherwise returns the full per-feature vector: if
+  /// `always_return_all` is set (protocols that send one packet covering
+  /// every motor, e.g. `LeloF1s`), every feature is present; otherwise only
+  /// the features that actually changed are `Some`, the rest `None`.
+  pub fn update_vibration(
+    &mut self,
+    msg: &VibrateCmd,
+    always_return_all: bool,
+  ) -> Result<Option<Vec<Option<u32>>>, ButtplugDeviceError> {
+    let mut changed = false;
+    let mut changed_values = vec![None; self.vibrations.len()];
+    for speed_cmd in &msg.speeds {
+      let index = speed_cmd.index as usize;
+      let step_count = *self.vibration_step_counts.get(index).ok_or_else(|| {
+        ButtplugDeviceError::DeviceNotAvailable(format!(
+          "Vibration index {} is out of range for this device.",
+          index
+        ))
+      })?;
+      let speed = (speed_cmd.speed * step_count as f64) as u32;
+      if self.vibrations[index] != Some(speed) {
+        changed = true;
+        changed_values[index] = Some(speed);
+      }
+      self.vibrations[index] = Some(speed);
+    }
+    if !changed {
+      return Ok(None);
+    }
+    Ok(Some(if always_return_all {
+      self.vibrations.clone()
+    } else {
+      changed_values
+    }))
+  }
+
+  pub fn update_rotation(
+    &mut self,
+    msg: &RotateCmd,
+  ) -> Result<Vec<Option<(u32, bool)>>, ButtplugDeviceError> {
+    let mut changed_values = vec![None; self.rotations.len()];
+    for rotate_cmd in &msg.rotations {
+      let index = rotate_cmd.index as usize;
+      let step_count = *self.rotation_step_counts.get(index).ok_or_else(|| {
+        ButtplugDeviceError::DeviceNotAvailable(format!(
+          "Rotation index {} is out of range for this device.",
+          index
+        ))
+      })?;
+      let speed = (rotate_cmd.speed * step_count as f64) as u32;
+      let value = (speed, rotate_cmd.clockwise);
+      if self.rotations[index] != Some(value) {
+        changed_values[index] = Some(value);
+      }
+      self.rotations[index] = Some(value);
+    }
+    Ok(changed_values)
+  }
+
+  /// Diffs `msg` against the last scalar values sent per (index, actuator
+  /// type), same diffing logic as `update_vibration`'s changed-only mode.
+  /// Returns `None` if nothing changed.
+  pub fn update_scalar(
+    &mut self,
+    msg: &ScalarCmd,
+  ) -> Result<Option<Vec<Option<(u32, ActuatorType)>>>, ButtplugDeviceError> {
+    let mut changed = false;
+    let mut changed_values = vec![None; self.scalars.len()];
+    for scalar_cmd in &msg.scalars {
+      let index = scalar_cmd.index as usize;
+      let step_count = *self.scalar_step_counts.get(index).ok_or_else(|| {
+        ButtplugDeviceError::DeviceNotAvailable(format!(
+          "Scalar index {} is out of range for this device.",
+          index
+        ))
+      })?;
+      let actuator_type = *self.scalar_actuator_types.get(index).ok_or_else(|| {
+        ButtplugDeviceError::DeviceNotAvailable(format!(
+          "Scalar index {} is out of range for this device.",
+          index
+        ))
+      })?;
+      if actuator_type != scalar_cmd.actuator_type {
+        return Err(ButtplugDeviceError::DeviceNotAvailable(format!(
+          "Scalar index {} does not support actuator type {:?}.",
+          index, scalar_cmd.actuator_type
+        )));
+      }
+      let scalar = (scalar_cmd.scalar * step_count as f64) as u32;
+      if self.scalars[index] != Some(scalar) {
+        changed = true;
+        changed_values[index] = Some((scalar, actuator_type));
+      }
+      self.scalars[index] = Some(scalar);
+    }
+    if !changed {
+      return Ok(None);
+    }
+    Ok(Some(changed_values))
+  }
+
+  /// Builds the `VibrateCmd`/`RotateCmd`/`ScalarCmd` messages that stop
+  /// every actuator on the device, for use when a `StopDeviceCmd` comes in.
+  pub fn get_stop_commands(&self) -> Vec<ButtplugDeviceCommandMessageUnion> {
+    let mut commands = vec![];
+    if !self.vibration_step_counts.is_empty() {
+      let subcommands = (0..self.vibration_step_counts.len())
+        .map(|i| VibrateSubcommand::new(i as u32, 0.0))
+        .collect();
+      commands.push(VibrateCmd::new(0, subcommands).into());
+    }
+    if !self.rotation_step_counts.is_empty() {
+      let subcommands = (0..self.rotation_step_counts.len())
+        .map(|i| RotateSubcommand::new(i as u32, 0.0, false))
+        .collect();
+      commands.push(RotateCmd::new(0, subcommands).into());
+    }
+    if !self.scalar_step_counts.is_empty() {
+      let subcommands = (0..self.scalar_step_counts.len())
+        .map(|i| ScalarSubcommand::new(i as u32, 0.0, self.scalar_actuator_types[i]))
+        .collect();
+      commands.push(ScalarCmd::new(0, subcommands).into());
+    }
+    commands
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::core::messages::MessageAttributes;
+
+  fn scalar_attrs(actuator_types: Vec<ActuatorType>, step_counts: Vec<u32>) -> MessageAttributesMap {
+    let mut attrs = MessageAttributesMap::new();
+    attrs.insert(
+      ButtplugDeviceMessageType::ScalarCmd,
+      MessageAttributes {
+        feature_count: Some(actuator_types.len() as u32),
+        step_count: Some(step_counts),
+        actuator_types: Some(actuator_types),
+        sensor_types: None,
+      },
+    );
+    attrs
+  }
+
+  #[test]
+  fn test_update_scalar_reports_only_changed_features() {
+    let mut manager = GenericCommandManager::new(&scalar_attrs(
+      vec![ActuatorType::Vibrate, ActuatorType::Constrict],
+      vec![20, 10],
+    ));
+    let changed = manager
+      .update_scalar(&ScalarCmd::new(
+        0,
+        vec![ScalarSubcommand::new(0, 0.5, ActuatorType::Vibrate)],
+      ))
+      .unwrap()
+      .expect("a changed scalar value should be reported");
+    assert_eq!(changed, vec![Some((10, ActuatorType::Vibrate)), None]);
+
+    // Resending the same value is a no-op.
+    assert!(manager
+      .update_scalar(&ScalarCmd::new(
+        0,
+        vec![ScalarSubcommand::new(0, 0.5, ActuatorType::Vibrate)],
+      ))
+      .unwrap()
+      .is_none());
+  }
+
+  #[test]
+  fn test_update_scalar_rejects_actuator_type_mismatch() {
+    let mut manager = GenericCommandManager::new(&scalar_attrs(vec![ActuatorType::Vibrate], vec![20]));
+    assert!(manager
+      .update_scalar(&ScalarCmd::new(
+        0,
+        vec![ScalarSubcommand::new(0, 0.5, ActuatorType::Constrict)],
+      ))
+      .is_err());
+  }
+
+  #[test]
+  fn test_get_stop_commands_includes_zeroed_scalar_cmd() {
+    let manager = GenericCommandManager::new(&scalar_attrs(
+      vec![ActuatorType::Vibrate, ActuatorType::Constrict],
+      vec![20, 10],
+    ));
+    let stop_commands = manager.get_stop_commands();
+    let scalar_stop = stop_commands
+      .iter()
+      .find_map(|cmd| match cmd {
+        ButtplugDeviceCommandMessageUnion::ScalarCmd(scalar_cmd) => Some(scalar_cmd),
+        _ => None,
+      })
+      .expect("a scalar-only device should get a zeroed ScalarCmd stop command");
+    assert_eq!(
+      scalar_stop.scalars,
+      vec![
+        ScalarSubcommand::new(0, 0.0, ActuatorType::Vibrate),
+        ScalarSubcommand::new(1, 0.0, ActuatorType::Constrict),
+      ]
+    );
+  }
+}
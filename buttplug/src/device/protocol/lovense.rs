@@ -1,4 +1,5 @@
 use super::{
+  await_with_timeout,
   ButtplugDeviceResultFuture,
   ButtplugProtocol,
   ButtplugProtocolCommandHandler,
@@ -7,7 +8,7 @@ use super::{
 use crate::{
   core::errors::ButtplugDeviceError,
   device::{
-    configuration_manager::DeviceProtocolConfiguration,
+    configuration_manager::{DeviceProtocolConfiguration, DEFAULT_HANDSHAKE_TIMEOUT},
     ButtplugDeviceEvent,
     DeviceSubscribeCmd,
     DeviceUnsubscribeCmd,
@@ -32,6 +33,7 @@ use std::sync::{
   atomic::{AtomicBool, Ordering},
   Arc,
 };
+use std::time::Duration;
 
 #[derive(ButtplugProtocol, ButtplugProtocolProperties)]
 pub struct Lovense {
@@ -40,10 +42,22 @@ pub struct Lovense {
   manager: Arc<Mutex<GenericCommandManager>>,
   stop_commands: Vec<ButtplugDeviceCommandMessageUnion>,
   rotation_direction: Arc<AtomicBool>,
+  /// How long `handle_sensor_read_cmd` waits for a query/response
+  /// handshake (e.g. `Battery;`) to get an answer before giving up.
+  /// Carried over from the handshake timeout negotiated in `try_create`.
+  handshake_timeout: Duration,
 }
 
 impl Lovense {
   pub(super) fn new(name: &str, message_attributes: MessageAttributesMap) -> Self {
+    Self::new_with_handshake_timeout(name, message_attributes, DEFAULT_HANDSHAKE_TIMEOUT)
+  }
+
+  pub(super) fn new_with_handshake_timeout(
+    name: &str,
+    message_attributes: MessageAttributesMap,
+    handshake_timeout: Duration,
+  ) -> Self {
     let manager = GenericCommandManager::new(&message_attributes);
 
     Self {
@@ -52,6 +66,7 @@ impl Lovense {
       stop_commands: manager.get_stop_commands(),
       manager: Arc::new(Mutex::new(manager)),
       rotation_direction: Arc::new(AtomicBool::new(false)),
+      handshake_timeout,
     }
   }
 }
@@ -70,13 +85,19 @@ impl ButtplugProtocolCreator for Lovense {
     let info_fut = device_impl.write_value(msg);
     let mut event_receiver = device_impl.get_event_receiver();
     let unsubscribe_fut = device_impl.unsubscribe(DeviceUnsubscribeCmd::new(Endpoint::Rx));
+    let handshake_timeout = configuration.handshake_timeout();
     Box::pin(async move {
       let identifier;
       subscribe_fut.await?;
       info_fut.await?;
-      // TODO Put some sort of very quick timeout here, we should just fail if
-      // we don't get something back quickly.
-      match event_receiver.next().await {
+      let notification = await_with_timeout(
+        event_receiver.next(),
+        handshake_timeout,
+        "Lovense",
+        "Did not get DeviceType return from Lovense device in time",
+      )
+      .await?;
+      match notification {
         Some(ButtplugDeviceEvent::Notification(_, n)) => {
           let type_response = std::str::from_utf8(&n).unwrap().to_owned();
           info!("Lovense Device Type Response: {}", type_response);
@@ -104,7 +125,7 @@ impl ButtplugProtocolCreator for Lovense {
       unsubscribe_fut.await?;
       let (names, attrs) = configuration.get_attributes(&identifier).unwrap();
       let name = names.get("en-us").unwrap();
-      Ok(Self::new_protocol(name, attrs))
+      Ok(Box::new(Self::new_with_handshake_timeout(name, attrs, handshake_timeout)) as Box<dyn ButtplugProtocol>)
     })
   }
 }
@@ -179,7 +200,311 @@ impl ButtplugProtocolCommandHandler for Lovense {
       Ok(messages::Ok::default().into())
     })
   }
+
+  fn handle_sensor_read_cmd(
+    &self,
+    device: Arc<Box<dyn DeviceImpl>>,
+    msg: messages::SensorReadCmd,
+  ) -> ButtplugDeviceResultFuture {
+    let handshake_timeout = self.handshake_timeout;
+    Box::pin(async move {
+      let subscribe_fut = device.subscribe(DeviceSubscribeCmd::new(Endpoint::Rx));
+      let mut event_receiver = device.get_event_receiver();
+      subscribe_fut.await?;
+      let battery_cmd = DeviceWriteCmd::new(Endpoint::Tx, b"Battery;".to_vec(), false);
+      device.write_value(battery_cmd).await?;
+      let notification = await_with_timeout(
+        event_receiver.next(),
+        handshake_timeout,
+        "Lovense",
+        "Did not get Battery return from Lovense device in time",
+      )
+      .await?;
+      let reading = match notification {
+        Some(ButtplugDeviceEvent::Notification(_, n)) => {
+          let text = std::str::from_utf8(&n).map_err(|_| {
+            ButtplugDeviceError::ProtocolSpecificError(
+              "Lovense",
+              "Lovense device battery reply was not valid UTF-8.",
+            )
+          })?;
+          // Lovense batteries reply with either a bare "<n>;" or a
+          // "Battery:<n>;"-style notification; take the numeric segment
+          // after the last colon (if any) and drop the trailing semicolon.
+          let value = text.trim_end_matches(';').rsplit(':').next().unwrap_or(text);
+          value.parse::<i32>().map_err(|_| {
+            ButtplugDeviceError::ProtocolSpecificError(
+              "Lovense",
+              "Lovense device battery reply was not a valid integer.",
+            )
+          })?
+        }
+        Some(ButtplugDeviceEvent::Removed) => {
+          return Err(
+            ButtplugDeviceError::ProtocolSpecificError(
+              "Lovense",
+              "Lovense Device disconnected while getting Battery info.",
+            )
+            .into(),
+          );
+        }
+        None => {
+          return Err(
+            ButtplugDeviceError::ProtocolSpecificError(
+              "Lovense",
+              "Did not get Battery return from Lovense device in time",
+            )
+            .into(),
+          );
+        }
+      };
+      device
+        .unsubscribe(DeviceUnsubscribeCmd::new(Endpoint::Rx))
+        .await?;
+      Ok(
+        messages::SensorReading::new(msg.device_index, msg.sensor_index, msg.sensor_type, vec![reading])
+          .into(),
+      )
+    })
+  }
 }
 
-// TODO Gonna need to add the ability to set subscribe data in tests before
-// writing Lovense tests. Oops.
+#[cfg(test)]
+mod test {
+  use super::{ButtplugProtocolCommandHandler, ButtplugProtocolCreator, ButtplugProtocolProperties, Lovense};
+  use crate::{
+    core::messages::{
+      ButtplugDeviceMessageType,
+      ButtplugServerMessage,
+      MessageAttributes,
+      MessageAttributesMap,
+      RotateCmd,
+      RotateSubcommand,
+      SensorReadCmd,
+      SensorType,
+      VibrateCmd,
+      VibrateSubcommand,
+    },
+    device::{
+      configuration_manager::DeviceProtocolConfiguration,
+      DeviceImplCommand,
+      DeviceWriteCmd,
+      Endpoint,
+    },
+    test::{check_recv_value, new_bluetoothle_test_device_impl},
+    util::async_manager,
+  };
+  use std::collections::HashMap;
+  use std::sync::Arc;
+
+  fn vibrate_attrs(motors: u32) -> MessageAttributesMap {
+    let mut attrs = MessageAttributesMap::new();
+    attrs.insert(
+      ButtplugDeviceMessageType::VibrateCmd,
+      MessageAttributes {
+        feature_count: Some(motors),
+        step_count: Some(vec![20; motors as usize]),
+        actuator_types: None,
+        sensor_types: None,
+      },
+    );
+    attrs
+  }
+
+  fn rotate_attrs(motors: u32) -> MessageAttributesMap {
+    let mut attrs = MessageAttributesMap::new();
+    attrs.insert(
+      ButtplugDeviceMessageType::RotateCmd,
+      MessageAttributes {
+        feature_count: Some(motors),
+        step_count: Some(vec![20; motors as usize]),
+        actuator_types: None,
+        sensor_types: None,
+      },
+    );
+    attrs
+  }
+
+  #[test]
+  pub fn test_lovense_device_type_handshake() {
+    async_manager::block_on(async move {
+      let (device_impl, test_device) = new_bluetoothle_test_device_impl().await.unwrap();
+      // try_create subscribes to Rx as soon as it's called, and queued
+      // notifications are delivered on subscribe, so queue the DeviceType
+      // response up front.
+      test_device.queue_notification(Endpoint::Rx, b"S:100".to_vec());
+      let mut locales = HashMap::new();
+      locales.insert("en-us".to_owned(), "Lovense Lush".to_owned());
+      let mut names = HashMap::new();
+      names.insert("S".to_owned(), (locales, vibrate_attrs(1)));
+      let configuration = DeviceProtocolConfiguration::new(names);
+      let protocol = Lovense::try_create(device_impl.as_ref(), configuration)
+        .await
+        .expect("Lovense should pick the \"S\" config off the DeviceType response");
+      assert_eq!(protocol.name(), "Lovense Lush");
+    });
+  }
+
+  #[test]
+  pub fn test_lovense_single_motor_vibrate() {
+    async_manager::block_on(async move {
+      let (device_impl, test_device) = new_bluetoothle_test_device_impl().await.unwrap();
+      let device_impl = Arc::new(device_impl);
+      let command_receiver = test_device
+        .get_endpoint_channel(&Endpoint::Tx)
+        .unwrap()
+        .receiver;
+      let protocol = Lovense::new("Lovense Lush", vibrate_attrs(1));
+      protocol
+        .handle_vibrate_cmd(
+          device_impl,
+          VibrateCmd::new(0, vec![VibrateSubcommand::new(0, 0.5)]),
+        )
+        .await
+        .unwrap();
+      check_recv_value(
+        &command_receiver,
+        DeviceImplCommand::Write(DeviceWriteCmd::new(
+          Endpoint::Tx,
+          b"Vibrate:10;".to_vec(),
+          false,
+        )),
+      )
+      .await;
+    });
+  }
+
+  #[test]
+  pub fn test_lovense_multi_motor_vibrate() {
+    async_manager::block_on(async move {
+      let (device_impl, test_device) = new_bluetoothle_test_device_impl().await.unwrap();
+      let device_impl = Arc::new(device_impl);
+      let command_receiver = test_device
+        .get_endpoint_channel(&Endpoint::Tx)
+        .unwrap()
+        .receiver;
+      let protocol = Lovense::new("Lovense Edge", vibrate_attrs(2));
+      protocol
+        .handle_vibrate_cmd(
+          device_impl,
+          VibrateCmd::new(
+            0,
+            vec![
+              VibrateSubcommand::new(0, 0.5),
+              VibrateSubcommand::new(1, 0.8),
+            ],
+          ),
+        )
+        .await
+        .unwrap();
+      // Differing motor speeds means we can't send a single `Vibrate:x;`,
+      // so each motor gets addressed individually.
+      check_recv_value(
+        &command_receiver,
+        DeviceImplCommand::Write(DeviceWriteCmd::new(
+          Endpoint::Tx,
+          b"Vibrate1:10;".to_vec(),
+          false,
+        )),
+      )
+      .await;
+      check_recv_value(
+        &command_receiver,
+        DeviceImplCommand::Write(DeviceWriteCmd::new(
+          Endpoint::Tx,
+          b"Vibrate2:16;".to_vec(),
+          false,
+        )),
+      )
+      .await;
+    });
+  }
+
+  #[test]
+  pub fn test_lovense_rotate_direction_toggle() {
+    async_manager::block_on(async move {
+      let (device_impl, test_device) = new_bluetoothle_test_device_impl().await.unwrap();
+      let device_impl = Arc::new(device_impl);
+      let command_receiver = test_device
+        .get_endpoint_channel(&Endpoint::Tx)
+        .unwrap()
+        .receiver;
+      let protocol = Lovense::new("Lovense Nora", rotate_attrs(1));
+      protocol
+        .handle_rotate_cmd(
+          device_impl.clone(),
+          RotateCmd::new(0, vec![RotateSubcommand::new(0, 0.5, true)]),
+        )
+        .await
+        .unwrap();
+      check_recv_value(
+        &command_receiver,
+        DeviceImplCommand::Write(DeviceWriteCmd::new(
+          Endpoint::Tx,
+          b"Rotate:10;".to_vec(),
+          false,
+        )),
+      )
+      .await;
+      // First command sets a direction, so we also expect a RotateChange.
+      check_recv_value(
+        &command_receiver,
+        DeviceImplCommand::Write(DeviceWriteCmd::new(
+          Endpoint::Tx,
+          b"RotateChange;".to_vec(),
+          false,
+        )),
+      )
+      .await;
+      protocol
+        .handle_rotate_cmd(
+          device_impl,
+          RotateCmd::new(0, vec![RotateSubcommand::new(0, 0.5, false)]),
+        )
+        .await
+        .unwrap();
+      check_recv_value(
+        &command_receiver,
+        DeviceImplCommand::Write(DeviceWriteCmd::new(
+          Endpoint::Tx,
+          b"Rotate:10;".to_vec(),
+          false,
+        )),
+      )
+      .await;
+      // Direction flipped, so we should see another RotateChange.
+      check_recv_value(
+        &command_receiver,
+        DeviceImplCommand::Write(DeviceWriteCmd::new(
+          Endpoint::Tx,
+          b"RotateChange;".to_vec(),
+          false,
+        )),
+      )
+      .await;
+    });
+  }
+
+  #[test]
+  pub fn test_lovense_battery_level() {
+    async_manager::block_on(async move {
+      let (device_impl, test_device) = new_bluetoothle_test_device_impl().await.unwrap();
+      let device_impl = Arc::new(device_impl);
+      // Queued before the read so it's waiting as soon as the protocol
+      // subscribes to Rx.
+      test_device.queue_notification(Endpoint::Rx, b"Battery:85;".to_vec());
+      let protocol = Lovense::new("Lovense Lush", vibrate_attrs(1));
+      let reading = protocol
+        .handle_sensor_read_cmd(
+          device_impl,
+          SensorReadCmd::new(0, 0, SensorType::Battery),
+        )
+        .await
+        .unwrap();
+      match reading {
+        ButtplugServerMessage::SensorReading(reading) => assert_eq!(reading.data, vec![85]),
+        _ => panic!("Expected a SensorReading reply"),
+      }
+    });
+  }
+}
@@ -0,0 +1,204 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2020 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Protocol implementations. Each submodule translates the generic
+//! `ButtplugDeviceCommandMessageUnion` message set into whatever a specific
+//! piece of hardware actually expects on the wire.
+
+pub mod generic_command_manager;
+pub mod lelof1s;
+pub mod lovense;
+pub mod prettylove;
+pub mod realov;
+
+use super::{
+  configuration_manager::DeviceProtocolConfiguration,
+  DeviceImpl,
+};
+use crate::core::{
+  errors::{ButtplugDeviceError, ButtplugError},
+  messages::{ButtplugDeviceCommandMessageUnion, ButtplugServerMessage, MessageAttributesMap},
+};
+use futures::future::{self, BoxFuture, Either};
+use futures_timer::Delay;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub type ButtplugDeviceResultFuture = BoxFuture<'static, Result<ButtplugServerMessage, ButtplugError>>;
+
+/// Races `fut` against `timeout`, so a handshake waiting on a response the
+/// device might never send (it disconnected mid-probe, it's not actually the
+/// protocol we guessed, ...) doesn't hang the whole scan forever. On expiry,
+/// returns a `ProtocolSpecificError` tagged with `protocol_name` and
+/// `timeout_message`.
+pub async fn await_with_timeout<T>(
+  fut: impl Future<Output = T>,
+  timeout: Duration,
+  protocol_name: &'static str,
+  timeout_message: &'static str,
+) -> Result<T, ButtplugError> {
+  match future::select(Box::pin(fut), Delay::new(timeout)).await {
+    Either::Left((value, _)) => Ok(value),
+    Either::Right(_) => {
+      Err(ButtplugDeviceError::ProtocolSpecificError(protocol_name, timeout_message).into())
+    }
+  }
+}
+
+/// Implemented (via the `ButtplugProtocol` derive) by every protocol struct.
+/// Dispatches an incoming command to the matching `handle_*` method on
+/// `ButtplugProtocolCommandHandler`.
+pub trait ButtplugProtocol:
+  ButtplugProtocolCommandHandler + ButtplugProtocolProperties + Send + Sync
+{
+  fn handle_command(
+    &self,
+    device: Arc<Box<dyn DeviceImpl>>,
+    command_message: ButtplugDeviceCommandMessageUnion,
+  ) -> ButtplugDeviceResultFuture {
+    match command_message {
+      ButtplugDeviceCommandMessageUnion::VibrateCmd(msg) => self.handle_vibrate_cmd(device, msg),
+      ButtplugDeviceCommandMessageUnion::RotateCmd(msg) => self.handle_rotate_cmd(device, msg),
+      ButtplugDeviceCommandMessageUnion::ScalarCmd(msg) => self.handle_scalar_cmd(device, msg),
+      ButtplugDeviceCommandMessageUnion::SensorReadCmd(msg) => {
+        self.handle_sensor_read_cmd(device, msg)
+      }
+      ButtplugDeviceCommandMessageUnion::StopDeviceCmd(_) => {
+        let futs: Vec<_> = self
+          .stop_commands()
+          .into_iter()
+          .map(|cmd| self.handle_command(device.clone(), cmd))
+          .collect();
+        Box::pin(async move {
+          for fut in futs {
+            fut.await?;
+          }
+          Ok(crate::core::messages::Ok::default().into())
+        })
+      }
+    }
+  }
+}
+
+/// Implemented (via the `ButtplugProtocolCreator` derive, or manually for
+/// protocols with a nontrivial handshake like `Lovense`) by every protocol
+/// struct, allowing it to be constructed once a device has been identified.
+pub trait ButtplugProtocolCreator {
+  fn new_protocol(name: &str, attrs: MessageAttributesMap) -> Box<dyn ButtplugProtocol>;
+
+  fn try_create(
+    device_impl: &dyn DeviceImpl,
+    configuration: DeviceProtocolConfiguration,
+  ) -> BoxFuture<'static, Result<Box<dyn ButtplugProtocol>, ButtplugError>>;
+}
+
+/// Implemented (via the `ButtplugProtocolProperties` derive) by every
+/// protocol struct; exposes the bookkeeping fields common to all of them.
+pub trait ButtplugProtocolProperties {
+  fn name(&self) -> &str;
+  fn message_attributes(&self) -> MessageAttributesMap;
+  fn stop_commands(&self) -> Vec<ButtplugDeviceCommandMessageUnion>;
+}
+
+/// Per-message handlers a protocol can override. Anything not overridden
+/// falls back to a "not supported" error (except `handle_scalar_cmd`, which
+/// falls back to the vibrate/rotate handlers), so new message types can be
+/// added here without breaking existing protocols.
+pub trait ButtplugProtocolCommandHandler: Send + Sync {
+  fn handle_vibrate_cmd(
+    &self,
+    _device: Arc<Box<dyn DeviceImpl>>,
+    _msg: crate::core::messages::VibrateCmd,
+  ) -> ButtplugDeviceResultFuture {
+    crate::core::errors::ButtplugError::from(ButtplugDeviceError::ProtocolNotImplemented(
+      "VibrateCmd".to_owned(),
+    ))
+    .into()
+  }
+
+  fn handle_rotate_cmd(
+    &self,
+    _device: Arc<Box<dyn DeviceImpl>>,
+    _msg: crate::core::messages::RotateCmd,
+  ) -> ButtplugDeviceResultFuture {
+    crate::core::errors::ButtplugError::from(ButtplugDeviceError::ProtocolNotImplemented(
+      "RotateCmd".to_owned(),
+    ))
+    .into()
+  }
+
+  fn handle_sensor_read_cmd(
+    &self,
+    _device: Arc<Box<dyn DeviceImpl>>,
+    _msg: crate::core::messages::SensorReadCmd,
+  ) -> ButtplugDeviceResultFuture {
+    crate::core::errors::ButtplugError::from(ButtplugDeviceError::ProtocolNotImplemented(
+      "SensorReadCmd".to_owned(),
+    ))
+    .into()
+  }
+
+  /// Protocols that haven't been converted to scalar actuators yet still
+  /// work against `ScalarCmd`: we split it back into the legacy
+  /// `VibrateCmd`/`RotateCmd` messages and dispatch those instead. Newer
+  /// protocols can override this to handle scalars directly.
+  fn handle_scalar_cmd(
+    &self,
+    device: Arc<Box<dyn DeviceImpl>>,
+    msg: crate::core::messages::ScalarCmd,
+  ) -> ButtplugDeviceResultFuture {
+    use crate::core::messages::{
+      ActuatorType,
+      RotateCmd,
+      RotateSubcommand,
+      VibrateCmd,
+      VibrateSubcommand,
+    };
+    let mut vibrate_subcommands = vec![];
+    let mut rotate_subcommands = vec![];
+    for scalar in &msg.scalars {
+      match scalar.actuator_type {
+        ActuatorType::Vibrate => {
+          vibrate_subcommands.push(VibrateSubcommand::new(scalar.index, scalar.scalar));
+        }
+        ActuatorType::Rotate => {
+          // ScalarCmd has no notion of rotation direction, so we default to
+          // clockwise. Protocols that care about direction should implement
+          // handle_scalar_cmd directly instead of relying on this fallback.
+          rotate_subcommands.push(RotateSubcommand::new(scalar.index, scalar.scalar, true));
+        }
+        ActuatorType::Oscillate | ActuatorType::Constrict | ActuatorType::Inflate => {
+          return crate::core::errors::ButtplugError::from(ButtplugDeviceError::ProtocolNotImplemented(
+            format!("{:?}", scalar.actuator_type),
+          ))
+          .into();
+        }
+      }
+    }
+    let device_index = msg.device_index;
+    let vibrate_fut = if !vibrate_subcommands.is_empty() {
+      Some(self.handle_vibrate_cmd(device.clone(), VibrateCmd::new(device_index, vibrate_subcommands)))
+    } else {
+      None
+    };
+    let rotate_fut = if !rotate_subcommands.is_empty() {
+      Some(self.handle_rotate_cmd(device, RotateCmd::new(device_index, rotate_subcommands)))
+    } else {
+      None
+    };
+    Box::pin(async move {
+      if let Some(fut) = vibrate_fut {
+        fut.await?;
+      }
+      if let Some(fut) = rotate_fut {
+        fut.await?;
+      }
+      Ok(crate::core::messages::Ok::default().into())
+    })
+  }
+}
@@ -13,12 +13,14 @@ use crate::{
     Endpoint,
   },
 };
+use async_mutex::Mutex;
 use std::sync::Arc;
 
 #[derive(ButtplugProtocol, ButtplugProtocolCreator, ButtplugProtocolProperties)]
 pub struct Realov {
   name: String,
   message_attributes: MessageAttributesMap,
+  manager: Arc<Mutex<GenericCommandManager>>,
   stop_commands: Vec<ButtplugDeviceCommandMessageUnion>,
 }
 
@@ -30,25 +32,109 @@ impl Realov {
       name: name.to_owned(),
       message_attributes,
       stop_commands: manager.get_stop_commands(),
+      manager: Arc::new(Mutex::new(manager)),
     }
   }
 }
 
 impl ButtplugProtocolCommandHandler for Realov {
+  // Realov hardware has a single vibrator, so `update_vibration`'s per-motor
+  // dedup always has at most one changed entry here; the loop is written the
+  // same way as multi-motor protocols like `Lovense` for consistency and in
+  // case a future multi-motor Realov shows up.
   fn handle_vibrate_cmd(
     &self,
     device: Arc<Box<dyn DeviceImpl>>,
     msg: messages::VibrateCmd,
   ) -> ButtplugDeviceResultFuture {
-    // TODO Convert to using generic command manager
-    let speed = (msg.speeds[0].speed * 50.0) as u8;
-    let msg = DeviceWriteCmd::new(Endpoint::Tx, [0xc5, 0x55, speed, 0xaa].to_vec(), false);
-    let fut = device.write_value(msg);
-    Box::pin(async {
-      fut.await?;
+    let manager = self.manager.clone();
+    Box::pin(async move {
+      let result = manager.lock().await.update_vibration(&msg, false)?;
+      let mut fut_vec = vec![];
+      if let Some(cmds) = result {
+        for cmd in cmds.iter() {
+          if let Some(speed) = cmd {
+            let write_msg = DeviceWriteCmd::new(
+              Endpoint::Tx,
+              vec![0xc5, 0x55, *speed as u8, 0xaa],
+              false,
+            );
+            fut_vec.push(device.write_value(write_msg));
+          }
+        }
+      }
+      for fut in fut_vec {
+        fut.await?;
+      }
       Ok(messages::Ok::default().into())
     })
   }
 }
 
-// TODO Write Tests
+#[cfg(test)]
+mod test {
+  use crate::{
+    core::messages::{StopDeviceCmd, VibrateCmd, VibrateSubcommand},
+    device::{DeviceImplCommand, DeviceWriteCmd, Endpoint},
+    test::{check_recv_value, new_bluetoothle_test_device},
+    util::async_manager,
+  };
+
+  #[test]
+  pub fn test_realov_protocol() {
+    async_manager::block_on(async move {
+      let (device, test_device) = new_bluetoothle_test_device("Realov").await.unwrap();
+      let command_receiver = test_device
+        .get_endpoint_channel(&Endpoint::Tx)
+        .unwrap()
+        .receiver;
+      device
+        .parse_message(VibrateCmd::new(0, vec![VibrateSubcommand::new(0, 0.5)]).into())
+        .await
+        .unwrap();
+      check_recv_value(
+        &command_receiver,
+        DeviceImplCommand::Write(DeviceWriteCmd::new(
+          Endpoint::Tx,
+          vec![0xc5, 0x55, 0x19, 0xaa],
+          false,
+        )),
+      )
+      .await;
+      // Unchanged, so no write should go out.
+      device
+        .parse_message(VibrateCmd::new(0, vec![VibrateSubcommand::new(0, 0.5)]).into())
+        .await
+        .unwrap();
+      assert!(command_receiver.is_empty());
+
+      device
+        .parse_message(VibrateCmd::new(0, vec![VibrateSubcommand::new(0, 1.0)]).into())
+        .await
+        .unwrap();
+      check_recv_value(
+        &command_receiver,
+        DeviceImplCommand::Write(DeviceWriteCmd::new(
+          Endpoint::Tx,
+          vec![0xc5, 0x55, 0x32, 0xaa],
+          false,
+        )),
+      )
+      .await;
+
+      device
+        .parse_message(StopDeviceCmd::new(0).into())
+        .await
+        .unwrap();
+      check_recv_value(
+        &command_receiver,
+        DeviceImplCommand::Write(DeviceWriteCmd::new(
+          Endpoint::Tx,
+          vec![0xc5, 0x55, 0x0, 0xaa],
+          false,
+        )),
+      )
+      .await;
+    });
+  }
+}
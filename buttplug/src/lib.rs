@@ -0,0 +1,19 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2020 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Buttplug is a framework for hardware control, designed specifically for
+//! intimate hardware (sex toys). For more info, check out
+//! <https://buttplug.io>.
+
+#[macro_use]
+extern crate log;
+
+pub mod core;
+pub mod device;
+#[cfg(test)]
+pub mod test;
+pub mod util;
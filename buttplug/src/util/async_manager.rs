@@ -0,0 +1,16 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2020 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Thin wrapper around the async executor used for tests and synchronous
+//! entry points, so the rest of the crate doesn't need to know which
+//! executor is in use.
+
+use std::future::Future;
+
+pub fn block_on<F: Future>(future: F) -> F::Output {
+  futures::executor::block_on(future)
+}